@@ -2,54 +2,312 @@ use anyhow::{anyhow, Result};
 use fnv::FnvHasher;
 use std::hash::Hasher;
 
-/// Computes the 2-character hex hash of a line's content (trimmed of trailing whitespace).
-pub fn hash_line(content: &str) -> String {
+/// Selects which hashing backend `hash_line`/`compute_file_hash` use.
+///
+/// `Fnv` is the original default and keeps its narrow 2-hex-char line hash for
+/// backwards compatibility; the other backends default to wider truncations
+/// since they're chosen specifically to reduce collisions on large files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum HashType {
+    Fnv,
+    Blake3,
+    Xxh3,
+    Crc32,
+}
+
+impl Default for HashType {
+    fn default() -> Self {
+        HashType::Fnv
+    }
+}
+
+impl HashType {
+    /// Number of hex characters a line hash is truncated to for this algorithm.
+    pub fn line_width(&self) -> usize {
+        match self {
+            HashType::Fnv => 2,
+            HashType::Blake3 => 8,
+            HashType::Xxh3 => 8,
+            HashType::Crc32 => 8,
+        }
+    }
+
+    /// Number of hex characters a whole-file hash is truncated to for this algorithm.
+    pub fn file_width(&self) -> usize {
+        match self {
+            HashType::Fnv => 6,
+            HashType::Blake3 => 16,
+            HashType::Xxh3 => 16,
+            HashType::Crc32 => 8,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HashType::Fnv => "fnv",
+            HashType::Blake3 => "blake3",
+            HashType::Xxh3 => "xxh3",
+            HashType::Crc32 => "crc32",
+        }
+    }
+}
+
+impl std::str::FromStr for HashType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "fnv" => Ok(HashType::Fnv),
+            "blake3" => Ok(HashType::Blake3),
+            "xxh3" => Ok(HashType::Xxh3),
+            "crc32" => Ok(HashType::Crc32),
+            other => Err(anyhow!("Unknown hash type: {}", other)),
+        }
+    }
+}
+
+/// Common interface for the hashing backends behind `HashType`, so
+/// `hash_line`/`compute_file_hash` only differ by which hasher they build.
+pub trait MyHasher {
+    fn update(&mut self, data: &[u8]);
+    fn finalize(&self) -> String;
+}
+
+struct FnvMyHasher {
+    hasher: FnvHasher,
+    width: usize,
+}
+
+impl MyHasher for FnvMyHasher {
+    fn update(&mut self, data: &[u8]) {
+        self.hasher.write(data);
+    }
+
+    fn finalize(&self) -> String {
+        truncate_u64(self.hasher.finish(), self.width)
+    }
+}
+
+struct Blake3MyHasher {
+    hasher: blake3::Hasher,
+    width: usize,
+}
+
+impl MyHasher for Blake3MyHasher {
+    fn update(&mut self, data: &[u8]) {
+        self.hasher.update(data);
+    }
+
+    fn finalize(&self) -> String {
+        self.hasher.finalize().to_hex()[..self.width].to_string()
+    }
+}
+
+struct Xxh3MyHasher {
+    hasher: xxhash_rust::xxh3::Xxh3,
+    width: usize,
+}
+
+impl MyHasher for Xxh3MyHasher {
+    fn update(&mut self, data: &[u8]) {
+        self.hasher.update(data);
+    }
+
+    fn finalize(&self) -> String {
+        truncate_u64(self.hasher.digest(), self.width)
+    }
+}
+
+struct Crc32MyHasher {
+    hasher: crc32fast::Hasher,
+    width: usize,
+}
+
+impl MyHasher for Crc32MyHasher {
+    fn update(&mut self, data: &[u8]) {
+        self.hasher.update(data);
+    }
+
+    fn finalize(&self) -> String {
+        let digest = self.hasher.clone().finalize() as u64;
+        truncate_u64(digest, self.width)
+    }
+}
+
+fn truncate_u64(value: u64, width: usize) -> String {
+    let bits = (width * 4).min(64);
+    let mask: u64 = if bits >= 64 { u64::MAX } else { (1u64 << bits) - 1 };
+    format!("{:0width$x}", value & mask, width = width)
+}
+
+fn new_hasher(hash_type: HashType, width: usize) -> Box<dyn MyHasher> {
+    match hash_type {
+        HashType::Fnv => Box::new(FnvMyHasher {
+            hasher: FnvHasher::default(),
+            width,
+        }),
+        HashType::Blake3 => Box::new(Blake3MyHasher {
+            hasher: blake3::Hasher::new(),
+            width,
+        }),
+        HashType::Xxh3 => Box::new(Xxh3MyHasher {
+            hasher: xxhash_rust::xxh3::Xxh3::new(),
+            width,
+        }),
+        HashType::Crc32 => Box::new(Crc32MyHasher {
+            hasher: crc32fast::Hasher::new(),
+            width,
+        }),
+    }
+}
+
+/// Computes the truncated hex hash of a line's content (trimmed of trailing whitespace).
+pub fn hash_line(content: &str, hash_type: HashType) -> String {
     let trimmed = content.trim_end();
-    let mut hasher = FnvHasher::default();
-    hasher.write(trimmed.as_bytes());
-    let hash = (hasher.finish() & 0xff) as u8;
-    format!("{:02x}", hash)
+    let mut hasher = new_hasher(hash_type, hash_type.line_width());
+    hasher.update(trimmed.as_bytes());
+    hasher.finalize()
 }
 
-/// Tags each line of the content with its line number and hash.
-pub fn tag_content(content: &str) -> String {
+/// Tags each line of the content with its line number, hash, and the hashes
+/// of its neighboring lines (`lineNum:hash:prevHash:nextHash|line`), so an
+/// agent can copy the context straight into an edit anchor.
+pub fn tag_content(content: &str, hash_type: HashType) -> String {
     let lines: Vec<&str> = content.lines().collect();
+    let hashes: Vec<String> = lines.iter().map(|l| hash_line(l, hash_type)).collect();
     let mut result = String::new();
-    for (i, line) in lines.iter().enumerate() {
-        let h = hash_line(line);
-        result.push_str(&format!("{}:{}|{}\n", i + 1, h, line));
+    for i in 0..lines.len() {
+        let prev = if i > 0 { hashes[i - 1].as_str() } else { "" };
+        let next = if i + 1 < lines.len() { hashes[i + 1].as_str() } else { "" };
+        result.push_str(&format!(
+            "{}:{}:{}:{}|{}\n",
+            i + 1,
+            hashes[i],
+            prev,
+            next,
+            lines[i]
+        ));
     }
     result
 }
 
-/// Computes a 6-character hex hash of the entire file content using FNV.
-/// This is shorter and more agent-friendly than SHA-256 while providing
-/// sufficient collision resistance for practical file editing scenarios.
-pub fn compute_file_hash(content: &str) -> String {
-    let mut hasher = FnvHasher::default();
-    hasher.write(content.as_bytes());
-    let hash = hasher.finish();
-    // Use 24 bits (6 hex chars) for reasonable collision resistance
-    format!("{:06x}", hash & 0xFFFFFF)
+/// Computes a truncated hex hash of the entire file content.
+/// This is shorter and more agent-friendly than a full digest while
+/// providing collision resistance appropriate to the chosen algorithm.
+pub fn compute_file_hash(content: &str, hash_type: HashType) -> String {
+    let mut hasher = new_hasher(hash_type, hash_type.file_width());
+    hasher.update(content.as_bytes());
+    hasher.finalize()
+}
+
+/// Number of leading bytes folded into a partial hash.
+pub const PARTIAL_BLOCK_SIZE: usize = 4096;
+
+/// Computes the partial hash of in-memory content: a hash of its leading
+/// `PARTIAL_BLOCK_SIZE` bytes plus its total length, folded together so a
+/// truncated or extended file still changes the partial hash even if its
+/// leading block is unchanged. This is a cheap early-exit check only; a
+/// full-hash mismatch is always authoritative.
+pub fn compute_partial_hash(content: &str, hash_type: HashType) -> (String, u64) {
+    let bytes = content.as_bytes();
+    let total_bytes = bytes.len() as u64;
+    let block = &bytes[..PARTIAL_BLOCK_SIZE.min(bytes.len())];
+    let mut hasher = new_hasher(hash_type, hash_type.file_width());
+    hasher.update(block);
+    hasher.update(&total_bytes.to_le_bytes());
+    (hasher.finalize(), total_bytes)
+}
+
+/// Computes the partial hash of a file on disk without reading the whole
+/// file: only its leading block is hashed, and at most one `BufReader`
+/// buffer (8 KiB by default) is read; the total length comes from filesystem
+/// metadata rather than a full read. Used to bail out of a "file modified" check early
+/// before paying for a full read-and-hash pass.
+pub fn hash_file_partial(path: &std::path::Path, hash_type: HashType) -> Result<(String, u64)> {
+    use std::io::Read;
+
+    let file = std::fs::File::open(path)?;
+    let total_bytes = file.metadata()?.len();
+    let mut reader = std::io::BufReader::new(file);
+    let mut block = vec![0u8; (PARTIAL_BLOCK_SIZE as u64).min(total_bytes) as usize];
+    reader.read_exact(&mut block)?;
+
+    let mut hasher = new_hasher(hash_type, hash_type.file_width());
+    hasher.update(&block);
+    hasher.update(&total_bytes.to_le_bytes());
+    Ok((hasher.finalize(), total_bytes))
 }
 
+/// Reads a file's full content while hashing it in a single streaming
+/// `BufReader` pass, rather than buffering the whole file and hashing it
+/// afterward. Returns the content alongside its full hash.
+pub fn read_and_hash_file(path: &std::path::Path, hash_type: HashType) -> Result<(String, String)> {
+    use std::io::Read;
+
+    let file = std::fs::File::open(path)?;
+    let mut reader = std::io::BufReader::new(file);
+    let mut hasher = new_hasher(hash_type, hash_type.file_width());
+    let mut bytes = Vec::new();
+    let mut buf = [0u8; 8192];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        bytes.extend_from_slice(&buf[..n]);
+    }
+
+    let content = String::from_utf8(bytes).map_err(|e| anyhow!("File is not valid UTF-8: {}", e))?;
+    let file_hash = hasher.finalize();
+    Ok((content, file_hash))
+}
+
+/// A line anchor, optionally carrying the hashes of its neighboring lines.
+/// The context hashes let `resolve_anchor` disambiguate a fuzzy hash match
+/// when a line shifted position instead of hard-failing.
 #[derive(Debug, Clone, PartialEq)]
 pub struct LineAnchor {
     pub line_num: usize,
     pub hash: String,
+    pub prev_hash: Option<String>,
+    pub next_hash: Option<String>,
 }
 
 impl std::str::FromStr for LineAnchor {
     type Err = anyhow::Error;
 
+    /// Parses `line_num:hash` or, with neighbor context,
+    /// `line_num:hash:prevHash:nextHash` (either side left empty if the line
+    /// has no such neighbor).
     fn from_str(s: &str) -> Result<Self> {
         let parts: Vec<&str> = s.split(':').collect();
-        if parts.len() != 2 {
-            return Err(anyhow!("Invalid anchor format. Expected 'line_num:hash'"));
+        match parts.len() {
+            2 => Ok(LineAnchor {
+                line_num: parts[0].parse::<usize>()?,
+                hash: parts[1].to_string(),
+                prev_hash: None,
+                next_hash: None,
+            }),
+            4 => Ok(LineAnchor {
+                line_num: parts[0].parse::<usize>()?,
+                hash: parts[1].to_string(),
+                prev_hash: non_empty(parts[2]),
+                next_hash: non_empty(parts[3]),
+            }),
+            _ => Err(anyhow!(
+                "Invalid anchor format. Expected 'line_num:hash' or 'line_num:hash:prevHash:nextHash'"
+            )),
         }
-        let line_num = parts[0].parse::<usize>()?;
-        let hash = parts[1].to_string();
-        Ok(LineAnchor { line_num, hash })
+    }
+}
+
+fn non_empty(s: &str) -> Option<String> {
+    if s.is_empty() {
+        None
+    } else {
+        Some(s.to_string())
     }
 }
 
@@ -68,43 +326,83 @@ pub struct HashlineOperation {
 }
 
 /// Resolves a line anchor to its current line index in the file.
-/// Provides exact match first, then fuzzy match by hash if exactly one match is found.
-pub fn resolve_anchor(lines: &[&str], anchor: &LineAnchor) -> Result<usize> {
+///
+/// Tries an exact match at `line_num` first, then a patch-style fuzz search
+/// among every line whose hash matches: each candidate is scored by how many
+/// of the anchor's context hashes (`prev_hash`/`next_hash`) agree with its
+/// actual neighbors, and the unique best-scoring candidate wins. Ties are
+/// broken by proximity to the expected `line_num`; only a tie that survives
+/// that tiebreaker is reported as ambiguous.
+pub fn resolve_anchor(lines: &[&str], anchor: &LineAnchor, hash_type: HashType) -> Result<usize> {
     // 1-indexed to 0-indexed
     let idx = anchor.line_num.saturating_sub(1);
 
     // 1. Exact match
-    if idx < lines.len() && hash_line(lines[idx]) == anchor.hash {
+    if idx < lines.len() && hash_line(lines[idx], hash_type) == anchor.hash {
         return Ok(idx);
     }
 
-    // 2. Fuzzy match (search for unique hash)
-    let mut matches = Vec::new();
-    for (i, line) in lines.iter().enumerate() {
-        if hash_line(line) == anchor.hash {
-            matches.push(i);
-        }
-    }
+    // 2. Fuzzy match: every line whose hash matches the anchor's.
+    let matches: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| hash_line(line, hash_type) == anchor.hash)
+        .map(|(i, _)| i)
+        .collect();
 
-    if matches.len() == 1 {
-        Ok(matches[0])
-    } else if matches.is_empty() {
-        Err(anyhow!(
+    if matches.is_empty() {
+        return Err(anyhow!(
             "Anchor {}:{} not found",
             anchor.line_num,
             anchor.hash
-        ))
+        ));
+    }
+
+    let context_score = |i: usize| -> usize {
+        let mut score = 0;
+        if let Some(prev) = &anchor.prev_hash {
+            if i > 0 && hash_line(lines[i - 1], hash_type) == *prev {
+                score += 1;
+            }
+        }
+        if let Some(next) = &anchor.next_hash {
+            if i + 1 < lines.len() && hash_line(lines[i + 1], hash_type) == *next {
+                score += 1;
+            }
+        }
+        score
+    };
+
+    let best_score = matches.iter().map(|&i| context_score(i)).max().unwrap();
+    let mut best: Vec<usize> = matches
+        .into_iter()
+        .filter(|&i| context_score(i) == best_score)
+        .collect();
+
+    if best.len() > 1 {
+        // Tiebreaker: prefer the candidate nearest the expected line number.
+        best.sort_by_key(|&i| (i as isize - idx as isize).abs());
+        let nearest_dist = (best[0] as isize - idx as isize).abs();
+        best.retain(|&i| (i as isize - idx as isize).abs() == nearest_dist);
+    }
+
+    if best.len() == 1 {
+        Ok(best[0])
     } else {
         Err(anyhow!(
             "Anchor {}:{} is ambiguous ({} matches found)",
             anchor.line_num,
             anchor.hash,
-            matches.len()
+            best.len()
         ))
     }
 }
 
-pub fn apply_operations(content: &str, operations: Vec<HashlineOperation>) -> Result<String> {
+pub fn apply_operations(
+    content: &str,
+    operations: Vec<HashlineOperation>,
+    hash_type: HashType,
+) -> Result<String> {
     let mut lines: Vec<String> = content.lines().map(|s| s.to_string()).collect();
 
     // Sort operations by anchor line number in reverse to avoid index shifts affecting subsequent operations.
@@ -116,9 +414,9 @@ pub fn apply_operations(content: &str, operations: Vec<HashlineOperation>) -> Re
     let mut resolved_ops: Vec<(usize, Option<usize>, OperationType, Option<String>)> = Vec::new();
 
     for op in operations {
-        let start_idx = resolve_anchor(&ref_lines, &op.anchor)?;
+        let start_idx = resolve_anchor(&ref_lines, &op.anchor, hash_type)?;
         let end_idx = if let Some(ref end) = op.end_anchor {
-            Some(resolve_anchor(&ref_lines, end)?)
+            Some(resolve_anchor(&ref_lines, end, hash_type)?)
         } else {
             None
         };
@@ -190,16 +488,30 @@ mod tests {
 
     #[test]
     fn test_hash_line() {
-        assert_eq!(hash_line("hello"), hash_line("hello  "));
-        assert_ne!(hash_line("hello"), hash_line("world"));
-        let h = hash_line("test");
+        assert_eq!(
+            hash_line("hello", HashType::Fnv),
+            hash_line("hello  ", HashType::Fnv)
+        );
+        assert_ne!(
+            hash_line("hello", HashType::Fnv),
+            hash_line("world", HashType::Fnv)
+        );
+        let h = hash_line("test", HashType::Fnv);
         assert_eq!(h.len(), 2);
     }
 
+    #[test]
+    fn test_hash_line_all_algorithms() {
+        for hash_type in [HashType::Fnv, HashType::Blake3, HashType::Xxh3, HashType::Crc32] {
+            let h = hash_line("test", hash_type);
+            assert_eq!(h.len(), hash_type.line_width());
+        }
+    }
+
     #[test]
     fn test_apply_operations() -> Result<()> {
         let content = "line1\nline2\nline3\n";
-        let h2 = hash_line("line2");
+        let h2 = hash_line("line2", HashType::Fnv);
         let ops = vec![HashlineOperation {
             op_type: OperationType::Replace,
             anchor: format!("2:{}", h2).parse()?,
@@ -207,8 +519,112 @@ mod tests {
             content: Some("new line 2".to_string()),
         }];
 
-        let result = apply_operations(content, ops)?;
+        let result = apply_operations(content, ops, HashType::Fnv)?;
         assert_eq!(result, "line1\nnew line 2\nline3\n");
         Ok(())
     }
+
+    #[test]
+    fn test_resolve_anchor_disambiguates_with_context() {
+        let hash_type = HashType::Fnv;
+        let lines = ["same", "unique_a", "same", "unique_b"];
+        let target_hash = hash_line("same", hash_type);
+
+        // Anchor claims line 2, but "same" actually sits at lines 1 and 3
+        // (0-indexed 0 and 2), so the exact match fails and both must be
+        // considered. Context should pick out index 2 uniquely.
+        let anchor = LineAnchor {
+            line_num: 2,
+            hash: target_hash,
+            prev_hash: Some(hash_line("unique_a", hash_type)),
+            next_hash: Some(hash_line("unique_b", hash_type)),
+        };
+
+        let idx = resolve_anchor(&lines, &anchor, hash_type).unwrap();
+        assert_eq!(idx, 2);
+    }
+
+    #[test]
+    fn test_resolve_anchor_still_ambiguous_without_distinguishing_context() {
+        let hash_type = HashType::Fnv;
+        let lines = ["same", "unique_a", "same", "unique_b"];
+        let target_hash = hash_line("same", hash_type);
+
+        let anchor = LineAnchor {
+            line_num: 2,
+            hash: target_hash,
+            prev_hash: None,
+            next_hash: None,
+        };
+
+        assert!(resolve_anchor(&lines, &anchor, hash_type).is_err());
+    }
+
+    #[test]
+    fn test_compute_partial_hash_changes_with_length() {
+        let hash_type = HashType::Fnv;
+        let short = "a".repeat(10);
+        let long = "a".repeat(20);
+
+        let (short_hash, short_len) = compute_partial_hash(&short, hash_type);
+        let (long_hash, long_len) = compute_partial_hash(&long, hash_type);
+
+        assert_eq!(short_len, 10);
+        assert_eq!(long_len, 20);
+        assert_ne!(short_hash, long_hash);
+    }
+
+    #[test]
+    fn test_hash_file_partial_matches_in_memory_partial_hash() -> Result<()> {
+        let hash_type = HashType::Fnv;
+        let path = std::env::temp_dir().join(format!("hashline_partial_test_{}.txt", std::process::id()));
+        let content = "x".repeat(5000);
+        std::fs::write(&path, &content)?;
+
+        let (disk_partial, disk_len) = hash_file_partial(&path, hash_type)?;
+        let (mem_partial, mem_len) = compute_partial_hash(&content, hash_type);
+
+        assert_eq!(disk_partial, mem_partial);
+        assert_eq!(disk_len, mem_len);
+
+        std::fs::remove_file(&path).ok();
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_and_hash_file_matches_compute_file_hash() -> Result<()> {
+        let hash_type = HashType::Fnv;
+        let path = std::env::temp_dir().join(format!("hashline_read_hash_test_{}.txt", std::process::id()));
+        let content = "hello\nworld\n".to_string();
+        std::fs::write(&path, &content)?;
+
+        let (read_content, read_hash) = read_and_hash_file(&path, hash_type)?;
+        assert_eq!(read_content, content);
+        assert_eq!(read_hash, compute_file_hash(&content, hash_type));
+
+        std::fs::remove_file(&path).ok();
+        Ok(())
+    }
+
+    #[test]
+    fn test_partial_hash_misses_change_past_block_but_full_hash_catches_it() {
+        // A change located entirely after PARTIAL_BLOCK_SIZE, with the file
+        // length unchanged, must not be masked by the partial hash: the full
+        // hash is always the authoritative check.
+        let hash_type = HashType::Fnv;
+        let mut modified = vec![b'a'; PARTIAL_BLOCK_SIZE + 100];
+        let original = String::from_utf8(modified.clone()).unwrap();
+        *modified.last_mut().unwrap() = b'b';
+        let modified = String::from_utf8(modified).unwrap();
+
+        let (orig_partial, orig_len) = compute_partial_hash(&original, hash_type);
+        let (mod_partial, mod_len) = compute_partial_hash(&modified, hash_type);
+        assert_eq!(orig_partial, mod_partial);
+        assert_eq!(orig_len, mod_len);
+
+        assert_ne!(
+            compute_file_hash(&original, hash_type),
+            compute_file_hash(&modified, hash_type)
+        );
+    }
 }
@@ -1,7 +1,13 @@
 mod hashline;
+mod roots;
 mod tools;
+mod vfs;
 
-use rmcp::{model::*, tool_handler, transport::stdio, ServerHandler, ServiceExt};
+use rmcp::{
+    model::*,
+    service::{NotificationContext, RoleServer},
+    tool_handler, transport::stdio, ServerHandler, ServiceExt,
+};
 
 use crate::tools::HashfileServer;
 
@@ -10,15 +16,79 @@ impl ServerHandler for HashfileServer {
     fn get_info(&self) -> ServerInfo {
         ServerInfo {
             instructions: Some("Hashfile MCP Server - provides reliable file editing using hash-anchored operations.".into()),
+            // `roots` is a *client* capability negotiated in its own
+            // `initialize` request, not something a server advertises here;
+            // `refresh_roots` checks the client's declared capability instead.
             capabilities: ServerCapabilities::builder().enable_tools().build(),
             ..Default::default()
         }
     }
+
+    async fn initialized(&self, context: NotificationContext<RoleServer>) {
+        self.refresh_roots(&context).await;
+    }
+
+    async fn on_roots_list_changed(&self, context: NotificationContext<RoleServer>) {
+        self.refresh_roots(&context).await;
+    }
+}
+
+impl HashfileServer {
+    /// Asks the client for its current roots and stores them, so every tool
+    /// call can be gated against `RootsManager::is_path_allowed`.
+    ///
+    /// Skips the request entirely for clients that never declared the
+    /// (optional) `roots` capability in their `initialize` request, rather
+    /// than relying on a runtime error from a call they don't support.
+    async fn refresh_roots(&self, context: &NotificationContext<RoleServer>) {
+        let supports_roots = context
+            .peer
+            .peer_info()
+            .map(|info| info.capabilities.roots.is_some())
+            .unwrap_or(false);
+
+        if !supports_roots {
+            return;
+        }
+
+        match context.peer.list_roots().await {
+            Ok(result) => self.set_roots(result.roots),
+            Err(e) => eprintln!("Failed to list client roots: {}", e),
+        }
+    }
+}
+
+/// Parses `HASHFILE_ALLOWED_ROOTS` (a `:`-separated list of absolute
+/// directories, same convention as `PATH`) into roots, for clients that
+/// don't implement the optional MCP roots capability. Without this, a
+/// client that never negotiates roots would have every tool call rejected
+/// with no way to allow any path at all.
+fn allowed_roots_from_env() -> Vec<Root> {
+    std::env::var("HASHFILE_ALLOWED_ROOTS")
+        .ok()
+        .map(|value| {
+            value
+                .split(':')
+                .filter(|dir| !dir.is_empty())
+                .map(|dir| Root {
+                    uri: format!("file://{}", dir),
+                    name: None,
+                })
+                .collect()
+        })
+        .unwrap_or_default()
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let service = HashfileServer::new().serve(stdio()).await?;
+    let server = HashfileServer::new();
+
+    let env_roots = allowed_roots_from_env();
+    if !env_roots.is_empty() {
+        server.set_roots(env_roots);
+    }
+
+    let service = server.serve(stdio()).await?;
     service.waiting().await?;
     Ok(())
 }
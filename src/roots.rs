@@ -61,3 +61,80 @@ impl RootsManager {
         Ok(false)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn root_for(dir: &std::path::Path) -> Root {
+        Root {
+            uri: format!("file://{}", dir.display()),
+            name: None,
+        }
+    }
+
+    fn unique_dir(label: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("hashfile_roots_{}_{}", label, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_allows_path_inside_root() {
+        let dir = unique_dir("allow");
+        let file = dir.join("inside.txt");
+        std::fs::write(&file, b"hello").unwrap();
+
+        let mut manager = RootsManager::new();
+        manager.set_roots(vec![root_for(&dir)]);
+
+        assert!(manager.is_path_allowed(file.to_str().unwrap()).unwrap());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_denies_path_outside_root() {
+        let dir = unique_dir("deny");
+        let outside = std::env::temp_dir().join(format!("hashfile_roots_outside_{}.txt", std::process::id()));
+        std::fs::write(&outside, b"hello").unwrap();
+
+        let mut manager = RootsManager::new();
+        manager.set_roots(vec![root_for(&dir)]);
+
+        assert!(!manager.is_path_allowed(outside.to_str().unwrap()).unwrap());
+
+        std::fs::remove_dir_all(&dir).ok();
+        std::fs::remove_file(&outside).ok();
+    }
+
+    #[test]
+    fn test_denies_path_traversal_out_of_root() {
+        let dir = unique_dir("traversal");
+        let nested = dir.join("nested");
+        std::fs::create_dir_all(&nested).unwrap();
+
+        // "nested/../.." resolves (after canonicalization) to the parent of
+        // `dir`, which is outside the configured root.
+        let escape = nested.join("..").join("..").join("escaped.txt");
+
+        let mut manager = RootsManager::new();
+        manager.set_roots(vec![root_for(&dir)]);
+
+        assert!(!manager.is_path_allowed(escape.to_str().unwrap()).unwrap());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_denies_everything_when_no_roots_configured() {
+        let dir = unique_dir("no_roots");
+        let file = dir.join("file.txt");
+        std::fs::write(&file, b"hello").unwrap();
+
+        let manager = RootsManager::new();
+        assert!(!manager.is_path_allowed(file.to_str().unwrap()).unwrap());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
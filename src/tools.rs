@@ -4,13 +4,20 @@ use rmcp::{
 };
 use serde::Deserialize;
 use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 
 use crate::hashline;
+use crate::roots::RootsManager;
+use crate::vfs::Vfs;
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct ReadTextInput {
     #[schemars(description = "Absolute path to the file to read")]
     pub path: String,
+    #[serde(default, rename = "hashType")]
+    #[schemars(description = "Hash algorithm to use: fnv, blake3, xxh3, or crc32 (default: fnv)")]
+    pub hash_type: hashline::HashType,
 }
 
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
@@ -25,18 +32,50 @@ pub struct WriteTextInput {
 pub struct EditTextInput {
     #[schemars(description = "Absolute path to the file to edit")]
     pub path: String,
-    #[schemars(description = "SHA-256 hash of the entire file content from the last read")]
+    #[schemars(description = "Hash of the entire file content from the last read or edit")]
     pub file_hash: String,
+    #[schemars(
+        description = "Partial hash (leading block + length) from the last read or edit, checked first as a cheap early exit"
+    )]
+    pub partial_hash: String,
+    #[serde(default, rename = "hashType")]
+    #[schemars(description = "Hash algorithm the file_hash and anchors were computed with: fnv, blake3, xxh3, or crc32 (default: fnv)")]
+    pub hash_type: hashline::HashType,
     pub operations: Vec<EditOperation>,
 }
 
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct OpenFileInput {
+    #[schemars(description = "Absolute path to the file to open into the in-memory VFS")]
+    pub path: String,
+    #[serde(default, rename = "hashType")]
+    #[schemars(description = "Hash algorithm to use: fnv, blake3, xxh3, or crc32 (default: fnv)")]
+    pub hash_type: hashline::HashType,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct FlushFileInput {
+    #[schemars(description = "Absolute path of the open file to persist to disk")]
+    pub path: String,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct DiscardFileInput {
+    #[schemars(description = "Absolute path of the open file whose in-memory edits should be dropped")]
+    pub path: String,
+}
+
 #[derive(Debug, Deserialize, schemars::JsonSchema)]
 pub struct EditOperation {
     #[schemars(description = "Type of operation: replace, insert_after, insert_before, or delete")]
     pub op_type: String,
-    #[schemars(description = "Anchor in lineNum:hash format")]
+    #[schemars(
+        description = "Anchor in lineNum:hash or lineNum:hash:prevHash:nextHash format; the context hashes help disambiguate a shifted line"
+    )]
     pub anchor: String,
-    #[schemars(description = "Optional end anchor in lineNum:hash format for range operations")]
+    #[schemars(
+        description = "Optional end anchor, same format as anchor, for range operations"
+    )]
     pub end_anchor: Option<String>,
     #[schemars(description = "New content for replace or insert operations")]
     pub content: Option<String>,
@@ -45,6 +84,8 @@ pub struct EditOperation {
 #[derive(Debug, Clone)]
 pub struct HashfileServer {
     pub tool_router: ToolRouter<Self>,
+    pub roots: Arc<Mutex<RootsManager>>,
+    pub vfs: Arc<Mutex<Vfs>>,
 }
 
 #[rmcp::tool_router]
@@ -52,6 +93,8 @@ impl HashfileServer {
     pub fn new() -> Self {
         Self {
             tool_router: Self::tool_router(),
+            roots: Arc::new(Mutex::new(RootsManager::new())),
+            vfs: Arc::new(Mutex::new(Vfs::new())),
         }
     }
 
@@ -60,9 +103,9 @@ impl HashfileServer {
     )]
     fn read_text_file(
         &self,
-        Parameters(ReadTextInput { path }): Parameters<ReadTextInput>,
+        Parameters(ReadTextInput { path, hash_type }): Parameters<ReadTextInput>,
     ) -> String {
-        match Self::read_text_file_impl(&path) {
+        match self.read_text_file_impl(&path, hash_type) {
             Ok(output) => output,
             Err(e) => format!("Error: {}", e),
         }
@@ -70,15 +113,47 @@ impl HashfileServer {
 
     #[rmcp::tool(description = "Write content to a file, creating it if it doesn't exist")]
     fn write_text_file(&self, Parameters(input): Parameters<WriteTextInput>) -> String {
-        match Self::write_text_file_impl(&input.path, &input.content) {
+        match self.write_text_file_impl(&input.path, &input.content) {
             Ok(msg) => msg,
             Err(e) => format!("Error: {}", e),
         }
     }
 
-    #[rmcp::tool(description = "Edit a file using hash-anchored operations")]
+    #[rmcp::tool(description = "Edit a file using hash-anchored operations, applied in-memory until flush_file persists them")]
     fn edit_text_file(&self, Parameters(input): Parameters<EditTextInput>) -> String {
-        match Self::edit_text_file_impl(&input.path, &input.file_hash, input.operations) {
+        match self.edit_text_file_impl(
+            &input.path,
+            &input.file_hash,
+            &input.partial_hash,
+            input.hash_type,
+            input.operations,
+        ) {
+            Ok(msg) => msg,
+            Err(e) => format!("Error: {}", e),
+        }
+    }
+
+    #[rmcp::tool(
+        description = "Open a file into the in-memory VFS so subsequent edit_text_file calls can be stacked without disk round-trips"
+    )]
+    fn open_file(&self, Parameters(OpenFileInput { path, hash_type }): Parameters<OpenFileInput>) -> String {
+        match self.open_file_impl(&path, hash_type) {
+            Ok(output) => output,
+            Err(e) => format!("Error: {}", e),
+        }
+    }
+
+    #[rmcp::tool(description = "Persist an open file's in-memory edits to disk")]
+    fn flush_file(&self, Parameters(FlushFileInput { path }): Parameters<FlushFileInput>) -> String {
+        match self.flush_file_impl(&path) {
+            Ok(msg) => msg,
+            Err(e) => format!("Error: {}", e),
+        }
+    }
+
+    #[rmcp::tool(description = "Discard an open file's in-memory edits without writing them to disk")]
+    fn discard_file(&self, Parameters(DiscardFileInput { path }): Parameters<DiscardFileInput>) -> String {
+        match self.discard_file_impl(&path) {
             Ok(msg) => msg,
             Err(e) => format!("Error: {}", e),
         }
@@ -86,37 +161,94 @@ impl HashfileServer {
 }
 
 impl HashfileServer {
-    fn read_text_file_impl(path: &str) -> anyhow::Result<String> {
-        let content = fs::read_to_string(path)?;
-        let tagged = hashline::tag_content(&content);
-        let file_hash = hashline::compute_file_hash(&content);
-        let total_lines = content.lines().count();
-
-        let output = format!(
-            "{}\n---\nhashline_version: 1\ntotal_lines: {}\nfile_hash: {}\n",
-            tagged, total_lines, file_hash
-        );
+    /// Replaces the server's known roots, e.g. after negotiating them over MCP.
+    pub fn set_roots(&self, roots: Vec<rmcp::model::Root>) {
+        if let Ok(mut guard) = self.roots.lock() {
+            guard.set_roots(roots);
+        }
+    }
 
-        Ok(output)
+    fn check_path_allowed(&self, path: &str) -> anyhow::Result<()> {
+        let roots = self
+            .roots
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Roots lock poisoned"))?;
+        if roots.is_path_allowed(path)? {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!(
+                "Path {} is outside allowed roots",
+                path
+            ))
+        }
     }
 
-    fn write_text_file_impl(path: &str, content: &str) -> anyhow::Result<String> {
+    fn read_text_file_impl(
+        &self,
+        path: &str,
+        hash_type: hashline::HashType,
+    ) -> anyhow::Result<String> {
+        self.check_path_allowed(path)?;
+        let cached = self.lock_vfs()?.open(Path::new(path), hash_type)?;
+        Ok(Self::format_read_output(&cached, hash_type))
+    }
+
+    fn write_text_file_impl(&self, path: &str, content: &str) -> anyhow::Result<String> {
+        self.check_path_allowed(path)?;
         fs::write(path, content)?;
+        self.lock_vfs()?.discard(Path::new(path));
         Ok(format!("Successfully wrote {} bytes to {}", content.len(), path))
     }
 
     fn edit_text_file_impl(
+        &self,
         path: &str,
         file_hash: &str,
+        partial_hash: &str,
+        hash_type: hashline::HashType,
         operations: Vec<EditOperation>,
     ) -> anyhow::Result<String> {
-        let current_content = fs::read_to_string(path)?;
-        let current_hash = hashline::compute_file_hash(&current_content);
+        self.check_path_allowed(path)?;
+        let path = Path::new(path);
+        let mut vfs = self.lock_vfs()?;
 
-        if current_hash != file_hash {
+        // Cheap early exit before paying for the authoritative full-hash
+        // check below: compare against the cached partial hash if the file
+        // is already open, or the on-disk partial hash otherwise. Previously
+        // this only ran for a not-yet-cached file, so partial_hash was
+        // silently ignored on every edit after the first despite being
+        // documented as checked on every call.
+        let partial_matches = match vfs.get(path) {
+            Some(cached) => cached.partial_hash == partial_hash,
+            None => hashline::hash_file_partial(path, hash_type)?.0 == partial_hash,
+        };
+        if !partial_matches {
             return Err(anyhow::anyhow!(
                 "File {} has been modified since it was last read. Please re-read the file.",
-                path
+                path.display()
+            ));
+        }
+
+        let cached = vfs.get_or_open(path, hash_type)?;
+
+        // A cached entry keeps the hash algorithm it was opened with; a call
+        // that disagrees (e.g. `hashType` defaulting to Fnv because the
+        // caller omitted it after opening with Blake3) must not silently
+        // flip `cached.hash_type`, or flush's base-hash comparison would be
+        // computed with the wrong algorithm and spuriously fail forever.
+        if cached.hash_type != hash_type {
+            return Err(anyhow::anyhow!(
+                "File {} is open with hash algorithm {}; edit_text_file calls must use the same algorithm it was opened with (got {})",
+                path.display(),
+                cached.hash_type.as_str(),
+                hash_type.as_str()
+            ));
+        }
+
+        if cached.file_hash != file_hash {
+            return Err(anyhow::anyhow!(
+                "File {} has been modified since it was last read. Please re-read the file.",
+                path.display()
             ));
         }
 
@@ -145,9 +277,274 @@ impl HashfileServer {
             });
         }
 
-        let new_content = hashline::apply_operations(&current_content, ops)?;
-        fs::write(path, &new_content)?;
+        let new_content = hashline::apply_operations(&cached.content, ops, hash_type)?;
+        let new_hash = vfs.update(path, new_content, hash_type)?;
+        let new_partial = vfs
+            .get(path)
+            .map(|c| c.partial_hash.clone())
+            .unwrap_or_default();
+
+        Ok(format!(
+            "Successfully edited {} in memory (file_hash: {}, partial_hash: {}); call flush_file to persist",
+            path.display(),
+            new_hash,
+            new_partial
+        ))
+    }
+
+    fn open_file_impl(&self, path: &str, hash_type: hashline::HashType) -> anyhow::Result<String> {
+        self.check_path_allowed(path)?;
+        let cached = self.lock_vfs()?.open(Path::new(path), hash_type)?;
+        Ok(Self::format_read_output(&cached, hash_type))
+    }
+
+    fn flush_file_impl(&self, path: &str) -> anyhow::Result<String> {
+        self.check_path_allowed(path)?;
+        let file_hash = self.lock_vfs()?.flush(Path::new(path))?;
+        Ok(format!(
+            "Successfully flushed {} to disk (file_hash: {})",
+            path, file_hash
+        ))
+    }
+
+    fn discard_file_impl(&self, path: &str) -> anyhow::Result<String> {
+        self.check_path_allowed(path)?;
+        if self.lock_vfs()?.discard(Path::new(path)) {
+            Ok(format!("Discarded in-memory edits for {}", path))
+        } else {
+            Ok(format!("{} was not open in the VFS", path))
+        }
+    }
+
+    fn lock_vfs(&self) -> anyhow::Result<std::sync::MutexGuard<'_, Vfs>> {
+        self.vfs
+            .lock()
+            .map_err(|_| anyhow::anyhow!("VFS lock poisoned"))
+    }
+
+    fn format_read_output(cached: &crate::vfs::CachedFile, hash_type: hashline::HashType) -> String {
+        let tagged = hashline::tag_content(&cached.content, hash_type);
+        let total_lines = cached.content.lines().count();
+        format!(
+            "{}\n---\nhashline_version: 1\ntotal_lines: {}\ntotal_bytes: {}\nfile_hash: {}\npartial_hash: {}\nhash_algo: {}\n",
+            tagged,
+            total_lines,
+            cached.total_bytes,
+            cached.file_hash,
+            cached.partial_hash,
+            hash_type.as_str()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unique_dir(label: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("hashfile_tools_{}_{}", label, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn server_with_root(dir: &Path) -> HashfileServer {
+        let server = HashfileServer::new();
+        server.set_roots(vec![rmcp::model::Root {
+            uri: format!("file://{}", dir.display()),
+            name: None,
+        }]);
+        server
+    }
+
+    #[test]
+    fn test_read_text_file_allows_path_inside_roots() {
+        let dir = unique_dir("read_allow");
+        let file = dir.join("file.txt");
+        fs::write(&file, "hello\nworld\n").unwrap();
+        let server = server_with_root(&dir);
+
+        let result = server.read_text_file_impl(file.to_str().unwrap(), hashline::HashType::Fnv);
+        assert!(result.is_ok());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_read_text_file_denies_path_outside_roots() {
+        let dir = unique_dir("read_deny");
+        let outside = std::env::temp_dir().join(format!("hashfile_tools_outside_{}.txt", std::process::id()));
+        fs::write(&outside, "content").unwrap();
+        let server = server_with_root(&dir);
+
+        let result = server.read_text_file_impl(outside.to_str().unwrap(), hashline::HashType::Fnv);
+        assert!(result.is_err());
+
+        fs::remove_dir_all(&dir).ok();
+        fs::remove_file(&outside).ok();
+    }
+
+    #[test]
+    fn test_write_text_file_denies_path_outside_roots() {
+        let dir = unique_dir("write_deny");
+        let outside = std::env::temp_dir().join(format!("hashfile_tools_write_outside_{}.txt", std::process::id()));
+        let server = server_with_root(&dir);
+
+        let result = server.write_text_file_impl(outside.to_str().unwrap(), "content");
+        assert!(result.is_err());
+        assert!(!outside.exists());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_edit_text_file_denies_path_outside_roots() {
+        let dir = unique_dir("edit_deny");
+        let outside = std::env::temp_dir().join(format!("hashfile_tools_edit_outside_{}.txt", std::process::id()));
+        fs::write(&outside, "line1\n").unwrap();
+        let server = server_with_root(&dir);
+
+        let result = server.edit_text_file_impl(
+            outside.to_str().unwrap(),
+            "deadbeef",
+            "deadbeef",
+            hashline::HashType::Fnv,
+            vec![],
+        );
+        assert!(result.is_err());
+
+        fs::remove_dir_all(&dir).ok();
+        fs::remove_file(&outside).ok();
+    }
+
+    #[test]
+    fn test_read_then_edit_round_trip_with_blake3() {
+        // The whole point of the pluggable-hasher request was to let
+        // edit_text_file verify against whichever algorithm read_text_file
+        // picked, not just the Fnv default — exercise that path end to end.
+        let dir = unique_dir("blake3_round_trip");
+        let file = dir.join("file.txt");
+        fs::write(&file, "line1\nline2\nline3\n").unwrap();
+        let server = server_with_root(&dir);
+        let hash_type = hashline::HashType::Blake3;
+
+        let read_output = server
+            .read_text_file_impl(file.to_str().unwrap(), hash_type)
+            .unwrap();
+        assert!(read_output.contains("hash_algo: blake3"));
+
+        let content = fs::read_to_string(&file).unwrap();
+        let file_hash = hashline::compute_file_hash(&content, hash_type);
+        let (partial_hash, _) = hashline::compute_partial_hash(&content, hash_type);
+        let line2_hash = hashline::hash_line("line2", hash_type);
+
+        let result = server.edit_text_file_impl(
+            file.to_str().unwrap(),
+            &file_hash,
+            &partial_hash,
+            hash_type,
+            vec![EditOperation {
+                op_type: "replace".to_string(),
+                anchor: format!("2:{}", line2_hash),
+                end_anchor: None,
+                content: Some("edited line2".to_string()),
+            }],
+        );
+        assert!(result.is_ok(), "edit failed: {:?}", result.err());
+
+        server.flush_file_impl(file.to_str().unwrap()).unwrap();
+        let on_disk = fs::read_to_string(&file).unwrap();
+        assert_eq!(on_disk, "line1\nedited line2\nline3\n");
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_edit_text_file_rejects_hash_type_mismatch_with_open_cache() {
+        // Opening/reading with one algorithm and then editing with another
+        // (or the `hashType` default, since the field is `#[serde(default)]`)
+        // must be rejected, not silently reinterpreted: letting it through
+        // would flip the cached entry's hash_type and break flush's
+        // base-hash comparison for the rest of the session.
+        let dir = unique_dir("hash_type_mismatch");
+        let file = dir.join("file.txt");
+        fs::write(&file, "line1\nline2\n").unwrap();
+        let server = server_with_root(&dir);
+        let blake3 = hashline::HashType::Blake3;
+
+        let cached = server.lock_vfs().unwrap().open(&file, blake3).unwrap();
+
+        let result = server.edit_text_file_impl(
+            file.to_str().unwrap(),
+            &cached.file_hash,
+            &cached.partial_hash,
+            hashline::HashType::Fnv,
+            vec![],
+        );
+
+        assert!(result.is_err());
+        assert!(result.unwrap_err().to_string().contains("hash algorithm"));
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_edit_text_file_checks_partial_hash_on_already_cached_file() {
+        // partial_hash is documented as "checked first as a cheap early
+        // exit" on every call, so a stale partial_hash must be caught even
+        // once the file is already open in the VFS, not just on the very
+        // first edit after a fresh read.
+        let dir = unique_dir("partial_hash_cached");
+        let file = dir.join("file.txt");
+        fs::write(&file, "line1\nline2\n").unwrap();
+        let server = server_with_root(&dir);
+        let hash_type = hashline::HashType::Fnv;
+
+        let cached = server.lock_vfs().unwrap().open(&file, hash_type).unwrap();
+
+        let result = server.edit_text_file_impl(
+            file.to_str().unwrap(),
+            &cached.file_hash,
+            "stale-partial-hash",
+            hash_type,
+            vec![],
+        );
+
+        assert!(result.is_err());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_edit_text_file_full_hash_catches_change_past_partial_block() {
+        // The partial hash only covers the leading PARTIAL_BLOCK_SIZE bytes
+        // plus length, so a change placed entirely after that block with the
+        // same overall length must still be caught by the full-hash check.
+        let dir = unique_dir("edit_two_tier");
+        let file = dir.join("big.txt");
+        let hash_type = hashline::HashType::Fnv;
+
+        let mut bytes = vec![b'a'; hashline::PARTIAL_BLOCK_SIZE + 100];
+        let original = String::from_utf8(bytes.clone()).unwrap();
+        fs::write(&file, &original).unwrap();
+
+        let (partial_hash, _) = hashline::compute_partial_hash(&original, hash_type);
+        let file_hash = hashline::compute_file_hash(&original, hash_type);
+
+        *bytes.last_mut().unwrap() = b'b';
+        let modified = String::from_utf8(bytes).unwrap();
+        fs::write(&file, &modified).unwrap();
+
+        let server = server_with_root(&dir);
+        let result = server.edit_text_file_impl(
+            file.to_str().unwrap(),
+            &file_hash,
+            &partial_hash,
+            hash_type,
+            vec![],
+        );
+
+        assert!(result.is_err());
 
-        Ok(format!("Successfully edited {}", path))
+        fs::remove_dir_all(&dir).ok();
     }
 }
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+
+use crate::hashline::{self, HashType};
+
+/// A file's content held in memory between `open_file`/`read_text_file` and
+/// the next `flush_file`/`discard_file`.
+///
+/// `content` is reference-counted so that returning/cloning a `CachedFile`
+/// (as `open`, `get_or_open` and `flush` do) never duplicates a large file's bytes.
+#[derive(Debug, Clone)]
+pub struct CachedFile {
+    pub content: Arc<str>,
+    pub file_hash: String,
+    pub partial_hash: String,
+    pub total_bytes: u64,
+    /// Full hash of the content as it was on disk when this entry was last
+    /// opened or flushed, used to detect external modification at flush time.
+    pub base_hash: String,
+    /// Partial hash counterpart of `base_hash`, checked first as a cheap
+    /// early exit before paying for the authoritative full-hash comparison.
+    pub base_partial_hash: String,
+    pub hash_type: HashType,
+    pub dirty: bool,
+}
+
+/// In-memory overlay over the filesystem. Lets a sequence of `edit_text_file`
+/// calls share one consistent version of a file's content without a disk
+/// round-trip between every edit; `flush_file` is what actually writes back.
+#[derive(Debug, Default)]
+pub struct Vfs {
+    files: HashMap<PathBuf, CachedFile>,
+}
+
+impl Vfs {
+    pub fn new() -> Self {
+        Self {
+            files: HashMap::new(),
+        }
+    }
+
+    /// Returns the cached entry for `path` without touching disk.
+    pub fn get(&self, path: &Path) -> Option<&CachedFile> {
+        self.files.get(path)
+    }
+
+    /// Reads `path` fresh from disk into the cache, discarding any previous
+    /// entry for it. Hashes in a single streaming pass rather than buffering
+    /// the whole file and hashing it afterward.
+    ///
+    /// Refuses to clobber an entry with unflushed edits (`dirty`) — callers
+    /// must `flush` or `discard` it first, or use `get_or_open` to keep
+    /// building on the cached version instead.
+    pub fn open(&mut self, path: &Path, hash_type: HashType) -> Result<CachedFile> {
+        if let Some(existing) = self.files.get(path) {
+            if existing.dirty {
+                return Err(anyhow!(
+                    "{} has unflushed edits; flush_file or discard_file it first",
+                    path.display()
+                ));
+            }
+        }
+
+        let (content, file_hash) = hashline::read_and_hash_file(path, hash_type)?;
+        let (partial_hash, total_bytes) = hashline::compute_partial_hash(&content, hash_type);
+        let cached = CachedFile {
+            content: Arc::from(content),
+            file_hash: file_hash.clone(),
+            partial_hash: partial_hash.clone(),
+            total_bytes,
+            base_hash: file_hash,
+            base_partial_hash: partial_hash,
+            hash_type,
+            dirty: false,
+        };
+        self.files.insert(path.to_path_buf(), cached.clone());
+        Ok(cached)
+    }
+
+    /// Returns the cached entry for `path`, opening it from disk first if it
+    /// isn't already cached.
+    pub fn get_or_open(&mut self, path: &Path, hash_type: HashType) -> Result<CachedFile> {
+        if let Some(cached) = self.files.get(path) {
+            return Ok(cached.clone());
+        }
+        self.open(path, hash_type)
+    }
+
+    /// Replaces the cached content for `path` (e.g. after `apply_operations`)
+    /// without touching disk, and returns the new file hash.
+    pub fn update(&mut self, path: &Path, content: String, hash_type: HashType) -> Result<String> {
+        let cached = self
+            .files
+            .get_mut(path)
+            .ok_or_else(|| anyhow!("{} is not open in the VFS", path.display()))?;
+        let (partial_hash, total_bytes) = hashline::compute_partial_hash(&content, hash_type);
+        cached.file_hash = hashline::compute_file_hash(&content, hash_type);
+        cached.partial_hash = partial_hash;
+        cached.total_bytes = total_bytes;
+        cached.content = Arc::from(content);
+        cached.hash_type = hash_type;
+        cached.dirty = true;
+        Ok(cached.file_hash.clone())
+    }
+
+    /// Persists the cached content to disk, failing if the on-disk content has
+    /// changed since the entry was last opened or flushed. Checks the cheap
+    /// partial hash first and only falls back to a full read-and-hash (still
+    /// the authoritative check) when the partial hash matches. If the file no
+    /// longer exists on disk, no check is made and it is written anew. Leaves
+    /// the entry cached (now clean) so further edits can keep building on it.
+    pub fn flush(&mut self, path: &Path) -> Result<String> {
+        let cached = self
+            .files
+            .get(path)
+            .ok_or_else(|| anyhow!("{} is not open in the VFS", path.display()))?
+            .clone();
+
+        if path.exists() {
+            let (on_disk_partial, _) = hashline::hash_file_partial(path, cached.hash_type)?;
+            if on_disk_partial != cached.base_partial_hash {
+                return Err(anyhow!(
+                    "File {} was modified on disk since it was opened; re-open before flushing",
+                    path.display()
+                ));
+            }
+
+            let on_disk = std::fs::read_to_string(path)?;
+            let on_disk_hash = hashline::compute_file_hash(&on_disk, cached.hash_type);
+            if on_disk_hash != cached.base_hash {
+                return Err(anyhow!(
+                    "File {} was modified on disk since it was opened; re-open before flushing",
+                    path.display()
+                ));
+            }
+        }
+
+        std::fs::write(path, cached.content.as_bytes())?;
+
+        let entry = self.files.get_mut(path).expect("checked above");
+        entry.base_hash = entry.file_hash.clone();
+        entry.base_partial_hash = entry.partial_hash.clone();
+        entry.dirty = false;
+
+        Ok(cached.file_hash)
+    }
+
+    /// Drops the cached entry for `path` without writing it to disk. Returns
+    /// `true` if an entry was actually cached.
+    pub fn discard(&mut self, path: &Path) -> bool {
+        self.files.remove(path).is_some()
+    }
+}